@@ -55,6 +55,13 @@ pub struct CombineFields<'infcx, 'gcx: 'infcx+'tcx, 'tcx: 'infcx> {
     pub trace: TypeTrace<'tcx>,
     pub cause: Option<ty::relate::Cause>,
     pub obligations: PredicateObligations<'tcx>,
+    /// Causes and spans recorded while generalizing, innermost last. The
+    /// `Generalizer` used by `generalize` pushes one `(Cause, Span)` here
+    /// for each variable or region it replaces with a fresh one (when
+    /// `cause` is set), and `instantiate` pops those frames again once its
+    /// comparison is finished. `cause_span` reads the top of this stack,
+    /// falling back on `trace.cause.span` when it is empty.
+    pub cause_stack: Vec<(ty::relate::Cause, Span)>,
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
@@ -109,6 +116,21 @@ impl<'infcx, 'gcx, 'tcx> InferCtxt<'infcx, 'gcx, 'tcx> {
                 self.unify_float_variable(!a_is_expected, v_id, v)
             }
 
+            // Relate const-generic variables to other consts
+            (&ty::TyInfer(ty::ConstVar(a_id)), &ty::TyInfer(ty::ConstVar(b_id))) => {
+                self.const_unification_table
+                    .borrow_mut()
+                    .unify_var_var(a_id, b_id)
+                    .map_err(|e| const_unification_error(a_is_expected, e))?;
+                Ok(a)
+            }
+            (&ty::TyInfer(ty::ConstVar(v_id)), &ty::TyConst(v)) => {
+                self.unify_const_variable(a_is_expected, v_id, v)
+            }
+            (&ty::TyConst(v), &ty::TyInfer(ty::ConstVar(v_id))) => {
+                self.unify_const_variable(!a_is_expected, v_id, v)
+            }
+
             // All other cases of inference are errors
             (&ty::TyInfer(_), _) |
             (_, &ty::TyInfer(_)) => {
@@ -150,6 +172,19 @@ impl<'infcx, 'gcx, 'tcx> InferCtxt<'infcx, 'gcx, 'tcx> {
             .map_err(|e| float_unification_error(vid_is_expected, e))?;
         Ok(self.tcx.mk_mach_float(val))
     }
+
+    fn unify_const_variable(&self,
+                            vid_is_expected: bool,
+                            vid: ty::ConstVid,
+                            val: &'tcx ty::Const<'tcx>)
+                            -> RelateResult<'tcx, Ty<'tcx>>
+    {
+        self.const_unification_table
+            .borrow_mut()
+            .unify_var_value(vid, val)
+            .map_err(|e| const_unification_error(vid_is_expected, e))?;
+        Ok(self.tcx.mk_ty(ty::TyConst(val)))
+    }
 }
 
 impl<'infcx, 'gcx, 'tcx> CombineFields<'infcx, 'gcx, 'tcx> {
@@ -173,6 +208,15 @@ impl<'infcx, 'gcx, 'tcx> CombineFields<'infcx, 'gcx, 'tcx> {
         Glb::new(self, a_is_expected)
     }
 
+    /// The span that a type error arising from the sub-relation currently
+    /// in progress should be attributed to: the innermost entry pushed onto
+    /// `cause_stack` by a nested `generalize` call, or the span of the
+    /// comparison as a whole if we are not nested inside any particular
+    /// field or argument position.
+    pub fn cause_span(&self) -> Span {
+        self.cause_stack.last().map(|&(_, span)| span).unwrap_or(self.trace.cause.span)
+    }
+
     /// Here dir is either EqTo, SubtypeOf, or SupertypeOf. The
     /// idea is that we should ensure that the type `a_ty` is equal
     /// to, a subtype of, or a supertype of (respectively) the type
@@ -207,23 +251,45 @@ impl<'infcx, 'gcx, 'tcx> CombineFields<'infcx, 'gcx, 'tcx> {
         // `'?2` and `?3` are fresh region/type inference
         // variables. (Down below, we will relate `a_ty <: b_ty`,
         // adding constraints like `'x: '?2` and `?1 <: ?3`.)
-        let b_ty = self.generalize(a_ty, b_vid, dir == EqTo)?;
+        // When `self.cause` is set, `generalize` pushes one `(cause, span)`
+        // entry onto `cause_stack` for every fresh inference variable it
+        // creates, recording the span
+        // of the specific sub-term each one replaced (see `Generalizer`).
+        // Remember how many frames were on the stack before we called it so
+        // we can drop exactly those once this comparison is finished.
+        let cause_stack_len = self.cause_stack.len();
+        let b_ty = match self.generalize(a_ty, b_vid, dir == EqTo) {
+            Ok(b_ty) => b_ty,
+            Err(e) => {
+                // A cyclic type (or any other generalization failure) can
+                // leave some of `generalize`'s frames on `cause_stack` from
+                // before the failure was detected; drop them so they don't
+                // get attributed to whatever unrelated `instantiate` call
+                // happens next.
+                self.cause_stack.truncate(cause_stack_len);
+                return Err(e);
+            }
+        };
         debug!("instantiate(a_ty={:?}, dir={:?}, b_vid={:?}, generalized b_ty={:?})",
                a_ty, dir, b_vid, b_ty);
         self.infcx.type_variables.borrow_mut().instantiate(b_vid, b_ty);
 
         // Finally, relate `b_ty` to `a_ty`, as described in previous comment.
-        //
-        // FIXME(#16847): This code is non-ideal because all these subtype
-        // relations wind up attributed to the same spans. We need
-        // to associate causes/spans with each of the relations in
-        // the stack to get this right.
-        match dir {
+        // The frames `generalize` just pushed stay live through this
+        // recursive relation, so a nested call to `instantiate` (relating
+        // a field or argument of `a_ty`/`b_ty`) sees the most recently
+        // pushed of them via `cause_span` and uses its span as the
+        // `Generalizer`'s span for its own operand. `cause_span` only
+        // returns the top frame; which of the fresh variables a failing
+        // sub-relation involves is not tracked.
+        let result = match dir {
             EqTo => self.equate(a_is_expected).relate(&a_ty, &b_ty),
             SubtypeOf => self.sub(a_is_expected).relate(&a_ty, &b_ty),
             SupertypeOf => self.sub(a_is_expected).relate_with_variance(
                 ty::Contravariant, &a_ty, &b_ty),
-        }?;
+        };
+        self.cause_stack.truncate(cause_stack_len);
+        result?;
 
         Ok(())
     }
@@ -238,24 +304,30 @@ impl<'infcx, 'gcx, 'tcx> CombineFields<'infcx, 'gcx, 'tcx> {
     /// Preconditions:
     ///
     /// - `for_vid` is a "root vid"
-    fn generalize(&self,
+    fn generalize(&mut self,
                   ty: Ty<'tcx>,
                   for_vid: ty::TyVid,
                   is_eq_relation: bool)
                   -> RelateResult<'tcx, Ty<'tcx>>
     {
+        let cause = self.cause.clone();
         let mut generalize = Generalizer {
             infcx: self.infcx,
-            span: self.trace.cause.span,
+            span: self.cause_span(),
             for_vid_sub_root: self.infcx.type_variables.borrow_mut().sub_root_var(for_vid),
             is_eq_relation: is_eq_relation,
-            cycle_detected: false
+            cycle_vid: None,
+            cause: cause,
+            cause_stack: &mut self.cause_stack,
         };
         let u = ty.fold_with(&mut generalize);
-        if generalize.cycle_detected {
-            Err(TypeError::CyclicTy)
-        } else {
-            Ok(u)
+        match generalize.cycle_vid {
+            // `ty` is the un-folded type, so it still shows `for_vid`'s
+            // occurrence of `cycle_vid` rather than the `tcx.types.err`
+            // substitute fold_ty replaced it with; this lets the error
+            // print the partially-built recursive shape, e.g. `_ = Vec<_>`.
+            Some(cycle_vid) => Err(TypeError::CyclicTy(cycle_vid, ty)),
+            None => Ok(u),
         }
     }
 }
@@ -265,7 +337,20 @@ struct Generalizer<'cx, 'gcx: 'cx+'tcx, 'tcx: 'cx> {
     span: Span,
     for_vid_sub_root: ty::TyVid,
     is_eq_relation: bool,
-    cycle_detected: bool,
+    /// Set to the `sub_root` of the first inference variable we encounter
+    /// that is related to `for_vid_sub_root` via subtyping, i.e. the
+    /// variable that closed the cycle. `None` means no cycle was found.
+    cycle_vid: Option<ty::TyVid>,
+    /// The cause this generalization is being performed for, if any;
+    /// paired with each replaced variable's own span and pushed onto
+    /// `cause_stack` below.
+    cause: Option<ty::relate::Cause>,
+    /// Borrowed from the `CombineFields` driving this generalization.
+    /// Each time we replace a type variable or a region with a fresh
+    /// variable (and `cause` is set), we push a span for it: the origin
+    /// span of the replaced type variable or `ReVar` region, or
+    /// `self.span` for any other region.
+    cause_stack: &'cx mut Vec<(ty::relate::Cause, Span)>,
 }
 
 impl<'cx, 'gcx, 'tcx> ty::fold::TypeFolder<'gcx, 'tcx> for Generalizer<'cx, 'gcx, 'tcx> {
@@ -286,7 +371,7 @@ impl<'cx, 'gcx, 'tcx> ty::fold::TypeFolder<'gcx, 'tcx> for Generalizer<'cx, 'gcx
                 if sub_vid == self.for_vid_sub_root {
                     // If sub-roots are equal, then `for_vid` and
                     // `vid` are related via subtyping.
-                    self.cycle_detected = true;
+                    self.cycle_vid = Some(sub_vid);
                     self.tcx().types.err
                 } else {
                     match variables.probe_root(vid) {
@@ -297,10 +382,14 @@ impl<'cx, 'gcx, 'tcx> ty::fold::TypeFolder<'gcx, 'tcx> for Generalizer<'cx, 'gcx
                         None => {
                             if !self.is_eq_relation {
                                 let origin = variables.origin(vid);
+                                let replaced_span = origin.span();
                                 let new_var_id = variables.new_var(false, origin, None);
                                 let u = self.tcx().mk_var(new_var_id);
                                 debug!("generalize: replacing original vid={:?} with new={:?}",
                                        vid, u);
+                                if let Some(cause) = self.cause.clone() {
+                                    self.cause_stack.push((cause, replaced_span));
+                                }
                                 u
                             } else {
                                 t
@@ -348,9 +437,20 @@ impl<'cx, 'gcx, 'tcx> ty::fold::TypeFolder<'gcx, 'tcx> for Generalizer<'cx, 'gcx
             }
         }
 
-        // FIXME: This is non-ideal because we don't give a
-        // very descriptive origin for this region variable.
-        self.infcx.next_region_var(MiscVariable(self.span))
+        // Prefer the span of the region variable we are actually replacing
+        // over `self.span` (the top frame of `cause_stack` when `generalize`
+        // began, or `trace.cause.span` if the stack was empty; see
+        // `CombineFields::cause_span`): a `ReVar` already carries the span
+        // of its own creation site, which is the specific sub-term this
+        // fresh region variable stands for.
+        let replaced_span = match *r {
+            ty::ReVar(vid) => self.infcx.region_vars.var_origin(vid).span(),
+            _ => self.span,
+        };
+        if let Some(cause) = self.cause.clone() {
+            self.cause_stack.push((cause, replaced_span));
+        }
+        self.infcx.next_region_var(MiscVariable(replaced_span))
     }
 }
 
@@ -387,3 +487,11 @@ fn float_unification_error<'tcx>(a_is_expected: bool,
     let (a, b) = v;
     TypeError::FloatMismatch(ty::relate::expected_found_bool(a_is_expected, &a, &b))
 }
+
+fn const_unification_error<'tcx>(a_is_expected: bool,
+                                 v: (&'tcx ty::Const<'tcx>, &'tcx ty::Const<'tcx>))
+                                 -> TypeError<'tcx>
+{
+    let (a, b) = v;
+    TypeError::ConstMismatch(ty::relate::expected_found_bool(a_is_expected, &a, &b))
+}